@@ -9,30 +9,154 @@ pub mod fine_tunes;
 pub mod moderations;
 mod audio;
 
-use anyhow::Result;
+use std::collections::HashMap;
 use std::io;
-use std::io::{Error, ErrorKind};
-use std::path::PathBuf;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 use std::pin::Pin;
+use std::sync::{Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use async_trait::async_trait;
-use bytes::Bytes;
-use reqwest::{Body, Client, multipart, RequestBuilder};
+use bytes::{Bytes, BytesMut};
+use rand::Rng;
+use reqwest::{Body, Client, multipart, RequestBuilder, Response, StatusCode};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest::multipart::Part;
 use serde::de::DeserializeOwned;
 use serde::ser::StdError;
 use serde::{Deserialize, Serialize};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt, SeekFrom};
+use tokio::sync::Semaphore;
 use tokio_stream::{Stream, StreamExt};
 use tokio_util::codec::{BytesCodec, FramedRead};
+use futures_util::stream::FuturesUnordered;
+use thiserror::Error;
 use with_id::WithRefId;
 use crate::structs::{ApiResponse, Model, ModelRequest, ModelsResponse};
 
+/// `sled` only allows a single open handle per path, so every `with_cache(path)`
+/// call across every client shares this path-keyed registry instead of racing to
+/// open the same path twice or, worse, having two different paths collapse onto a
+/// single process-wide database.
+static CACHE_REGISTRY: OnceLock<Mutex<HashMap<PathBuf, sled::Db>>> = OnceLock::new();
+
+pub type Result<T, E = ApiError> = std::result::Result<T, E>;
+
+/// Errors produced by the request traits in this crate. `Api` and `RateLimited` come
+/// from a parsed, non-2xx OpenAI response; the rest are transport/local failures.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    /// A non-2xx response whose body was successfully parsed as OpenAI's
+    /// `{ "error": { ... } }` envelope.
+    #[error("openai api error {status}: {message}")]
+    Api {
+        status: StatusCode,
+        code: Option<String>,
+        message: String,
+        param: Option<String>,
+        r#type: Option<String>
+    },
+    /// A `429 Too Many Requests` response, carrying the `Retry-After` header's value
+    /// if it sent one.
+    #[error("rate limited by openai api")]
+    RateLimited { retry_after: Option<Duration> },
+    /// An expected response header was missing, so the response can't be trusted.
+    #[error("response is missing the {0} header")]
+    MissingHeader(&'static str),
+    /// An SSE stream's underlying byte stream ended without a terminating
+    /// `data: [DONE]` event, so the response may have been truncated mid-stream.
+    #[error("stream ended before a terminating [DONE] event was received")]
+    TruncatedStream,
+    #[error(transparent)]
+    Transport(#[from] reqwest::Error),
+    #[error("failed to decode json: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Cache(#[from] sled::Error),
+    /// A caller-supplied header value (e.g. an organization or project id) isn't
+    /// valid header bytes.
+    #[error("invalid header value: {0}")]
+    InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
+    /// Catch-all for errors surfaced through a generic associated type (e.g.
+    /// [`AsyncTryFrom::Error`]) that can't be converted to a more specific variant.
+    #[error("{0}")]
+    Other(#[from] anyhow::Error)
+}
+
+#[derive(Deserialize)]
+struct ApiErrorEnvelope {
+    error: ApiErrorDetail
+}
+
+#[derive(Deserialize)]
+struct ApiErrorDetail {
+    message: String,
+    #[serde(rename = "type")]
+    r#type: Option<String>,
+    param: Option<String>,
+    code: Option<String>
+}
+
+/// Turns a non-2xx response into a structured [`ApiError`], parsing OpenAI's
+/// `{ "error": { ... } }` body when present instead of surfacing a raw parse failure.
+async fn api_error_from_response(response: Response) -> ApiError {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = response.headers().get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|s| s.parse::<u64>().ok())
+            .map(Duration::from_secs);
+        return ApiError::RateLimited { retry_after };
+    }
+    match response.json::<ApiErrorEnvelope>().await {
+        Ok(envelope) => ApiError::Api {
+            status,
+            code: envelope.error.code,
+            message: envelope.error.message,
+            param: envelope.error.param,
+            r#type: envelope.error.r#type
+        },
+        Err(e) => ApiError::Transport(e)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OpenAiClient {
     url:String,
     key:String,
-    client:Client
+    client:Client,
+    timeout: Option<Duration>,
+    retry_policy: RetryPolicy,
+    cache: Option<sled::Tree>,
+    cache_ttl: Option<Duration>,
+    owns_client: bool,
+    default_headers: HeaderMap
+}
+
+/// Controls how a request is retried when it hits a transient error: a connection
+/// failure, a timeout, or an HTTP 429/500/502/503 response. `max_attempts: 1` (the
+/// default) disables retries and sends the request exactly once.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30)
+        }
+    }
 }
 
 impl OpenAiClient {
@@ -40,8 +164,10 @@ impl OpenAiClient {
     const URL: &'static str = "https://api.openai.com/v1";
 
     pub fn new(key: &str)->Self{
-        let client = Client::new();
-        OpenAiClient::with_client(key,&client)
+        let mut instance = OpenAiClient::with_url_and_client(key,OpenAiClient::URL,&Client::new());
+        instance.owns_client = true;
+        instance.rebuild_owned_client();
+        instance
     }
 
     /// reqwest library recommends reusing single client,
@@ -51,18 +177,230 @@ impl OpenAiClient {
     }
 
     pub fn with_url(key: &str, url: &str) -> Self {
-        let client = Client::new();
-        OpenAiClient::with_url_and_client(key,url,&client)
+        let mut instance = OpenAiClient::with_url_and_client(key,url,&Client::new());
+        instance.owns_client = true;
+        instance.rebuild_owned_client();
+        instance
     }
 
 
     pub fn with_url_and_client(key: &str, url: &str, client: &Client)->Self{
+        let mut default_headers = HeaderMap::new();
+        default_headers.insert(USER_AGENT, HeaderValue::from_static(concat!("openai-api-rust/", env!("CARGO_PKG_VERSION"))));
         OpenAiClient {
             url: url.to_string(),
             key: key.to_string(),
-            client: client.clone()
+            client: client.clone(),
+            timeout: None,
+            retry_policy: RetryPolicy::default(),
+            cache: None,
+            cache_ttl: None,
+            owns_client: false,
+            default_headers
+        }
+    }
+
+    /// Applies `timeout` to every request sent through this client.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Replaces the [`RetryPolicy`] used for transient failures on every request
+    /// sent through this client.
+    pub fn with_retries(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the `OpenAI-Organization` header sent with every request.
+    pub fn with_organization(self, organization: &str) -> Result<Self> {
+        self.with_default_header("openai-organization", organization)
+    }
+
+    /// Sets the `OpenAI-Project` header sent with every request.
+    pub fn with_project(self, project: &str) -> Result<Self> {
+        self.with_default_header("openai-project", project)
+    }
+
+    /// Merges `headers` into the set of headers sent with every request, for proxies
+    /// or gateways that require headers beyond organization/project/user-agent.
+    pub fn with_default_headers(mut self, headers: HeaderMap) -> Self {
+        self.default_headers.extend(headers);
+        self.rebuild_owned_client();
+        self
+    }
+
+    fn with_default_header(mut self, name: &'static str, value: &str) -> Result<Self> {
+        let name = HeaderName::from_static(name);
+        let value = HeaderValue::from_str(value)?;
+        self.default_headers.insert(name, value);
+        self.rebuild_owned_client();
+        Ok(self)
+    }
+
+    /// When this client built its own [`Client`] (via [`new`](Self::new) or
+    /// [`with_url`](Self::with_url)), rebuilds it so `default_headers` is baked in via
+    /// `reqwest`'s own `default_headers`, covering every trait's send path uniformly.
+    /// Clients supplied by the caller (via [`with_client`](Self::with_client) or
+    /// [`with_url_and_client`](Self::with_url_and_client)) are left untouched; for those,
+    /// `default_headers` is instead applied per-request.
+    fn rebuild_owned_client(&mut self) {
+        if self.owns_client {
+            self.client = Client::builder()
+                .default_headers(self.default_headers.clone())
+                .build()
+                .expect("failed to build http client with default headers");
         }
     }
+
+    /// Opts this client into response caching for [`JsonRequest`]s that implement
+    /// [`Cacheable`], backed by a `sled` database at `path`. Each distinct (canonicalized)
+    /// `path` gets its own `sled::Db`, shared by every client that points at it, since
+    /// `sled` only allows a single open handle per path.
+    pub fn with_cache(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        // Ensure the path exists *before* canonicalizing, so the same literal path
+        // always canonicalizes to the same registry key regardless of whether this is
+        // the first call to create it or a later call reusing it.
+        std::fs::create_dir_all(path)?;
+        let canonical = path.canonicalize()?;
+        let registry = CACHE_REGISTRY.get_or_init(|| Mutex::new(HashMap::new()));
+        let mut registry = registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let db = match registry.get(&canonical) {
+            Some(db) => db.clone(),
+            None => {
+                let db = sled::open(&canonical)?;
+                registry.insert(canonical, db.clone());
+                db
+            }
+        };
+        self.cache = Some(db.open_tree("json_request_cache")?);
+        Ok(self)
+    }
+
+    /// Expires cached entries older than `ttl`. Has no effect unless [`with_cache`](Self::with_cache)
+    /// is also used.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+}
+
+/// Marker trait opting a [`JsonRequest`] into response caching via
+/// [`JsonRequest::run_cached`]. Intended for deterministic endpoints - embeddings,
+/// moderations, or completions run at `temperature = 0` - where identical requests
+/// are expected to produce identical responses.
+pub trait Cacheable {}
+
+fn cache_key<T: Serialize>(endpoint: &str, request: &T) -> Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    hasher.update(endpoint.as_bytes());
+    hasher.update(serde_json::to_vec(request)?);
+    Ok(hasher.finalize().into())
+}
+
+/// Prefixes `body` with the current unix timestamp so cached entries can be expired by age.
+fn encode_cache_entry(body: &[u8]) -> Vec<u8> {
+    let stored_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let mut entry = Vec::with_capacity(8 + body.len());
+    entry.extend_from_slice(&stored_at.to_le_bytes());
+    entry.extend_from_slice(body);
+    entry
+}
+
+/// Returns the cached body if present and not older than `ttl`.
+fn decode_cache_entry(entry: &[u8], ttl: Option<Duration>) -> Option<&[u8]> {
+    if entry.len() < 8 {
+        return None;
+    }
+    let (stamp, body) = entry.split_at(8);
+    let stored_at = u64::from_le_bytes(stamp.try_into().ok()?);
+    if let Some(ttl) = ttl {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        if now.saturating_sub(stored_at) > ttl.as_secs() {
+            return None;
+        }
+    }
+    Some(body)
+}
+
+/// Applies `client`'s default headers (when it doesn't already own a [`Client`] built
+/// with them baked in, see [`OpenAiClient::rebuild_owned_client`]) and timeout to
+/// `request`.
+fn apply_client_defaults(client: &OpenAiClient, mut request: RequestBuilder) -> RequestBuilder {
+    if !client.owns_client {
+        request = request.headers(client.default_headers.clone());
+    }
+    if let Some(timeout) = client.timeout {
+        request = request.timeout(timeout);
+    }
+    request
+}
+
+/// Sends `request` exactly once - no retries - applying `client`'s default headers and
+/// timeout, and classifying a non-2xx response via [`api_error_from_response`]. For
+/// requests that stream a body once and can't be safely rebuilt and resent, like a
+/// multipart upload.
+async fn send_once(client: &OpenAiClient, request: RequestBuilder) -> Result<Response> {
+    let response = apply_client_defaults(client, request).send().await?;
+    if response.error_for_status_ref().is_err() {
+        return Err(api_error_from_response(response).await);
+    }
+    Ok(response)
+}
+
+/// Sends the request built by `build` (a closure producing a fresh [`RequestBuilder`]
+/// so the request can be rebuilt on every retry), applying `client`'s timeout and
+/// retrying on connection errors, timeouts, and HTTP 429/500/502/503 responses
+/// according to `client`'s [`RetryPolicy`]. A `Retry-After` header on any of those
+/// retried responses is honored in place of the policy's computed backoff.
+async fn send_with_retry<F, Fut>(client: &OpenAiClient, build: F) -> Result<Response>
+    where
+        F: Fn() -> Fut,
+        Fut: std::future::Future<Output=Result<RequestBuilder>>
+{
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let request = apply_client_defaults(client, build().await?);
+        match request.send().await {
+            Ok(response) => {
+                if response.error_for_status_ref().is_ok() {
+                    return Ok(response);
+                }
+                let retryable = matches!(response.status(),
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::INTERNAL_SERVER_ERROR
+                    | StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE);
+                if retryable && attempt < client.retry_policy.max_attempts {
+                    let retry_after = response.headers().get(reqwest::header::RETRY_AFTER).cloned();
+                    tokio::time::sleep(retry_delay(&client.retry_policy, attempt, retry_after.as_ref())).await;
+                    continue;
+                }
+                return Err(api_error_from_response(response).await);
+            }
+            Err(e) => {
+                if (e.is_connect() || e.is_timeout()) && attempt < client.retry_policy.max_attempts {
+                    tokio::time::sleep(retry_delay(&client.retry_policy, attempt, None)).await;
+                } else {
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
+
+/// Computes the delay before the next retry attempt: the `Retry-After` header value
+/// if present (seconds only), otherwise exponential backoff from the policy's
+/// `initial_backoff`, capped at `max_backoff`, with up to 50% jitter added.
+fn retry_delay(policy: &RetryPolicy, attempt: u32, retry_after: Option<&reqwest::header::HeaderValue>) -> Duration {
+    if let Some(seconds) = retry_after.and_then(|v| v.to_str().ok()).and_then(|s| s.parse::<u64>().ok()) {
+        return Duration::from_secs(seconds);
+    }
+    let backoff = policy.initial_backoff
+        .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+        .min(policy.max_backoff);
+    backoff + Duration::from_millis(rand::thread_rng().gen_range(0..=backoff.as_millis() as u64 / 2))
 }
 
 
@@ -73,15 +411,145 @@ pub trait JsonRequest<TRes: DeserializeOwned>: Serialize + Sized + Sync{
 
     async fn run(&self, client:&OpenAiClient) -> Result<ApiResponse<TRes>>{
         let final_url = client.url.to_owned()+Self::ENDPOINT;
-        let res = client.client.post(final_url)
-            .bearer_auth(client.key.clone())
-            .json(self)
-            .send()
-            .await?
+        let res = send_with_retry(client, || async {
+            Ok(client.client.post(final_url.as_str())
+                .bearer_auth(client.key.clone())
+                .json(self))
+        }).await?
             .json::<ApiResponse<TRes>>()
             .await?;
         Ok(res)
     }
+
+    /// Like [`run`](Self::run), but checks `client`'s response cache (set up via
+    /// [`OpenAiClient::with_cache`]) before sending, and stores the response on a miss.
+    /// Only available where `Self: `[`Cacheable`], so opt in by implementing that marker
+    /// trait on deterministic request types.
+    async fn run_cached(&self, client:&OpenAiClient) -> Result<ApiResponse<TRes>>
+        where Self: Cacheable
+    {
+        let cache = match &client.cache {
+            Some(cache) => cache,
+            None => return self.run(client).await
+        };
+        let key = cache_key(Self::ENDPOINT, self)?;
+        if let Some(entry) = cache.get(key)? {
+            if let Some(body) = decode_cache_entry(&entry, client.cache_ttl) {
+                return Ok(serde_json::from_slice(body)?);
+            }
+            cache.remove(key)?;
+        }
+        let final_url = client.url.to_owned()+Self::ENDPOINT;
+        let body = send_with_retry(client, || async {
+            Ok(client.client.post(final_url.as_str())
+                .bearer_auth(client.key.clone())
+                .json(self))
+        }).await?
+            .bytes()
+            .await?;
+        cache.insert(key, encode_cache_entry(&body))?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+}
+
+
+/// Variant of [`JsonRequest`] for endpoints that support `stream: true`,
+/// where OpenAI replies with a `text/event-stream` of incremental chunks
+/// instead of a single JSON body.
+#[async_trait]
+pub trait JsonStreamRequest<TChunk: DeserializeOwned + Send + 'static>: Serialize + Sized + Sync{
+
+    const ENDPOINT: &'static str;
+
+    async fn run_stream(&self, client:&OpenAiClient) -> Result<Pin<Box<dyn Stream<Item=Result<TChunk>> + Send>>>{
+        let final_url = client.url.to_owned()+Self::ENDPOINT;
+        let mut body = serde_json::to_value(self)?;
+        if let Some(obj) = body.as_object_mut(){
+            obj.insert("stream".to_string(), Value::Bool(true));
+        }
+        let byte_stream = send_with_retry(client, || async {
+            Ok(client.client.post(final_url.as_str())
+                .bearer_auth(client.key.clone())
+                .json(&body))
+        }).await?
+            .bytes_stream();
+        Ok(Box::pin(SseStream::new(Box::pin(byte_stream))))
+    }
+}
+
+#[derive(Debug)]
+enum SseEvent {
+    Data(Bytes),
+    Done
+}
+
+/// Pulls the next complete `\n\n`-delimited SSE event out of `buffer`, if any is buffered yet.
+fn next_sse_event(buffer: &mut BytesMut) -> Option<SseEvent> {
+    loop {
+        let boundary = buffer.windows(2).position(|w| w == b"\n\n")?;
+        let event = buffer.split_to(boundary + 2);
+        let event = &event[..boundary];
+        for line in event.split(|&b| b == b'\n') {
+            if let Some(data) = line.strip_prefix(b"data: ") {
+                return Some(if data == b"[DONE]" {
+                    SseEvent::Done
+                } else {
+                    SseEvent::Data(Bytes::copy_from_slice(data))
+                });
+            }
+        }
+        // block had no `data:` line (e.g. a comment/keep-alive) - keep draining
+    }
+}
+
+/// Adapts a raw byte stream into a stream of deserialized SSE data payloads.
+struct SseStream<TChunk> {
+    inner: Pin<Box<dyn Stream<Item=std::result::Result<Bytes, reqwest::Error>> + Send>>,
+    buffer: BytesMut,
+    done: bool,
+    _chunk: std::marker::PhantomData<TChunk>
+}
+
+impl<TChunk> SseStream<TChunk> {
+    fn new(inner: Pin<Box<dyn Stream<Item=std::result::Result<Bytes, reqwest::Error>> + Send>>) -> Self {
+        SseStream { inner, buffer: BytesMut::new(), done: false, _chunk: std::marker::PhantomData }
+    }
+}
+
+impl<TChunk: DeserializeOwned> Stream for SseStream<TChunk> {
+    type Item = Result<TChunk>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.done {
+                return Poll::Ready(None);
+            }
+            if let Some(event) = next_sse_event(&mut this.buffer) {
+                return match event {
+                    SseEvent::Done => {
+                        this.done = true;
+                        Poll::Ready(None)
+                    }
+                    SseEvent::Data(data) => Poll::Ready(Some(
+                        serde_json::from_slice::<TChunk>(&data).map_err(ApiError::from)
+                    ))
+                };
+            }
+            match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => this.buffer.extend_from_slice(&chunk),
+                Poll::Ready(Some(Err(e))) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(ApiError::from(e))));
+                }
+                Poll::Ready(None) => {
+                    this.done = true;
+                    return Poll::Ready(Some(Err(ApiError::TruncatedStream)));
+                }
+                Poll::Pending => return Poll::Pending
+            }
+        }
+    }
 }
 
 
@@ -97,10 +565,10 @@ pub trait ByUrlRequest<TRes: DeserializeOwned>:WithRefId<str>+Sync{
 
     async fn run(&self, client:&OpenAiClient)-> Result<ApiResponse<TRes>>{
         let final_url = client.url.to_owned()+Self::ENDPOINT+self.id()+Self::SUFFIX;
-        let res = Self::builder(client,final_url)
-            .bearer_auth(client.key.clone())
-            .send()
-            .await?
+        let res = send_with_retry(client, || async {
+            Ok(Self::builder(client,final_url.clone())
+                .bearer_auth(client.key.clone()))
+        }).await?
             .json::<ApiResponse<TRes>>()
             .await?;
         Ok(res)
@@ -115,10 +583,10 @@ pub trait GetRequest:DeserializeOwned {
 
     async fn get(client:&OpenAiClient)-> Result<ApiResponse<Self>>{
         let final_url = client.url.to_owned()+Self::ENDPOINT;
-        let res = client.client.get(final_url)
-            .bearer_auth(client.key.clone())
-            .send()
-            .await?
+        let res = send_with_retry(client, || async {
+            Ok(client.client.get(final_url.as_str())
+                .bearer_auth(client.key.clone()))
+        }).await?
             .json::<ApiResponse<Self>>()
             .await?;
         Ok(res)
@@ -132,15 +600,87 @@ pub trait FormRequest<TRes: DeserializeOwned> : AsyncTryInto<multipart::Form>+Cl
 
     async fn run(&self, client:&OpenAiClient)-> Result<ApiResponse<TRes>>{
         let final_url =  client.url.to_owned()+Self::ENDPOINT;
-        let res = client.client.post(final_url)
-            .bearer_auth(client.key.clone())
-            .multipart(AsyncTryInto::try_into(self.clone()).await?)
-            .send()
-            .await?
+        let res = send_with_retry(client, || async {
+            let form = AsyncTryInto::try_into(self.clone()).await.map_err(|e| ApiError::Other(anyhow::Error::new(e)))?;
+            Ok(client.client.post(final_url.as_str())
+                .bearer_auth(client.key.clone())
+                .multipart(form))
+        }).await?
             .json::<ApiResponse<TRes>>()
             .await?;
         Ok(res)
     }
+
+    /// Like [`run`](Self::run), but reports upload progress through `progress` as the
+    /// underlying file part is streamed, for implementations whose [`AsyncTryFrom`]
+    /// overrides [`try_from_with_progress`](AsyncTryFrom::try_from_with_progress).
+    ///
+    /// The multipart body streams the file exactly once, so unlike [`run`](Self::run)
+    /// this does not retry on transient failures - only `client`'s timeout applies.
+    async fn run_with_progress(&self, client:&OpenAiClient, progress: ProgressCallback)-> Result<ApiResponse<TRes>>{
+        let final_url =  client.url.to_owned()+Self::ENDPOINT;
+        let form = AsyncTryInto::try_into_with_progress(self.clone(), progress).await
+            .map_err(|e| ApiError::Other(anyhow::Error::new(e)))?;
+        let request = client.client.post(final_url)
+            .bearer_auth(client.key.clone())
+            .multipart(form);
+        let response = send_once(client, request).await?;
+        Ok(response.json::<ApiResponse<TRes>>().await?)
+    }
+}
+
+/// Shared body of [`DownloadRequest::download_to_file`] and
+/// [`DownloadRequest::download_to_file_with_progress`]: sends `final_url`, streaming the
+/// body to a sibling `<target_path>.part` file and renaming it to `target_path` only on
+/// completion - so a prior run finishing fully is never mistaken for a partial one. If
+/// `<target_path>.part` already exists from an interrupted run, its length is sent as a
+/// `Range: bytes=<offset>-` request header and, if the server answers `206 Partial
+/// Content`, new bytes are appended; a `200 OK` restarts from zero.
+async fn download_response_to_file(
+    client: &OpenAiClient,
+    final_url: &str,
+    target_path: &str,
+    mut progress: Option<ProgressCallback>
+) -> Result<()> {
+    let part_path = format!("{target_path}.part");
+    let existing = tokio::fs::metadata(&part_path).await.map(|m| m.len()).unwrap_or(0);
+    let response = send_with_retry(client, || async {
+        let mut builder = client.client.get(final_url)
+            .bearer_auth(client.key.clone());
+        if existing > 0 {
+            builder = builder.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+        }
+        Ok(builder)
+    }).await?;
+    let resuming = existing > 0 && response.status() == StatusCode::PARTIAL_CONTENT;
+    let total = if resuming {
+        response.content_length().map(|len| existing + len)
+    } else {
+        response.content_length()
+    };
+    let mut file = if resuming {
+        if !response.headers().contains_key(reqwest::header::CONTENT_RANGE) {
+            return Err(ApiError::MissingHeader("Content-Range"));
+        }
+        let mut file = OpenOptions::new().write(true).open(&part_path).await?;
+        file.seek(SeekFrom::End(0)).await?;
+        file
+    } else {
+        File::create(&part_path).await?
+    };
+    let mut bytes_so_far = if resuming { existing } else { 0 };
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        bytes_so_far += chunk.len() as u64;
+        if let Some(progress) = progress.as_mut() {
+            progress(bytes_so_far, total);
+        }
+        file.write_all(&chunk).await?;
+    }
+    drop(file);
+    tokio::fs::rename(&part_path, target_path).await?;
+    Ok(())
 }
 
 #[async_trait(?Send)]
@@ -151,26 +691,59 @@ pub trait DownloadRequest: WithRefId<str>{
 
     async fn download(&self, client:&OpenAiClient) -> Result<Pin<Box<dyn Stream<Item=Result<Bytes, reqwest::Error>>>>>{
         let final_url = client.url.to_owned()+Self::ENDPOINT+self.id()+Self::SUFFIX;
-        let res = client.client.get(final_url)
-            .bearer_auth(client.key.clone())
-            .send()
-            .await?
-            .error_for_status()?
+        let res = send_with_retry(client, || async {
+            Ok(client.client.get(final_url.as_str())
+                .bearer_auth(client.key.clone()))
+        }).await?
             .bytes_stream();
         Ok(Box::pin(res))
     }
 
+    /// Downloads to `target_path`, streaming into a sibling `<target_path>.part` file
+    /// and renaming it to `target_path` only once the download completes, so a fully
+    /// completed previous run is never mistaken for a partial one. If a `.part` file
+    /// from an interrupted run already exists, its length is sent as a `Range:
+    /// bytes=<offset>-` request header and, if the server answers `206 Partial Content`,
+    /// new bytes are appended; a server that ignores the range and answers `200 OK`
+    /// causes a restart from zero.
     async fn download_to_file(&self, client:&OpenAiClient, target_path:&str) -> Result<()>{
-        let mut file = File::create(target_path).await?;
-        let mut stream = self.download(client).await?;
-        while let Some(chunk) = stream.next().await {
-            file.write_all(&chunk?).await?;
-        }
-        Ok(())
+        let final_url = client.url.to_owned()+Self::ENDPOINT+self.id()+Self::SUFFIX;
+        download_response_to_file(client, &final_url, target_path, None).await
+    }
+
+    /// Like [`download_to_file`](Self::download_to_file), but calls `progress` with
+    /// `(bytes_so_far, total)` as each chunk arrives, and resumes a partial download the
+    /// same way `download_to_file` does.
+    async fn download_to_file_with_progress(&self, client:&OpenAiClient, target_path:&str, progress: ProgressCallback) -> Result<()>{
+        let final_url = client.url.to_owned()+Self::ENDPOINT+self.id()+Self::SUFFIX;
+        download_response_to_file(client, &final_url, target_path, Some(progress)).await
     }
 
 }
 
+/// Downloads `items` (a request paired with its destination path) concurrently,
+/// bounding the number of in-flight downloads to `max_concurrent` via a [`Semaphore`].
+/// Results are returned in the same order as `items`.
+pub async fn download_many<R: DownloadRequest>(
+    client: &OpenAiClient,
+    items: &[(R, String)],
+    max_concurrent: usize
+) -> Vec<Result<()>> {
+    let semaphore = Semaphore::new(max_concurrent.max(1));
+    let mut pending: FuturesUnordered<_> = items.iter().enumerate().map(|(index, (request, target_path))| {
+        let semaphore = &semaphore;
+        async move {
+            let _permit = semaphore.acquire().await.expect("semaphore is never closed");
+            (index, request.download_to_file(client, target_path).await)
+        }
+    }).collect();
+
+    let mut results: Vec<Option<Result<()>>> = (0..items.len()).map(|_| None).collect();
+    while let Some((index, result)) = pending.next().await {
+        results[index] = Some(result);
+    }
+    results.into_iter().map(|r| r.expect("every item is visited exactly once")).collect()
+}
 
 impl GetRequest for ModelsResponse {
     const ENDPOINT: &'static str = "/models";
@@ -188,6 +761,13 @@ pub trait AsyncTryFrom<T>: Sized {
     type Error: 'static+StdError+Send+Sync;
 
     async fn try_from(value: T) -> Result<Self, Self::Error>;
+
+    /// Like [`try_from`](Self::try_from), but given a progress hook for implementations
+    /// that stream large payloads (e.g. file uploads) and can report bytes transferred
+    /// as they go. Defaults to ignoring the hook and delegating to `try_from`.
+    async fn try_from_with_progress(value: T, _progress: ProgressCallback) -> Result<Self, Self::Error>{
+        Self::try_from(value).await
+    }
 }
 
 #[async_trait]
@@ -196,6 +776,8 @@ pub trait AsyncTryInto<T>: Sized {
     type Error: 'static+StdError+Send+Sync;
 
     async fn try_into(self) -> Result<T, Self::Error>;
+
+    async fn try_into_with_progress(self, progress: ProgressCallback) -> Result<T, Self::Error>;
 }
 
 #[async_trait]
@@ -209,18 +791,175 @@ impl<T, U> AsyncTryInto<U> for T
     async fn try_into(self) -> Result<U, Self::Error>{
         U::try_from(self).await
     }
+
+    async fn try_into_with_progress(self, progress: ProgressCallback) -> Result<U, Self::Error>{
+        U::try_from_with_progress(self, progress).await
+    }
+}
+
+/// Reports `(bytes_so_far, total)` for a transfer in progress. `total` is `None`
+/// when the size isn't known upfront.
+pub type ProgressCallback = Box<dyn FnMut(u64, Option<u64>) + Send>;
+
+/// Wraps a byte-chunk stream, invoking a [`ProgressCallback`] with the running byte
+/// count (and `total`, if known) as each chunk is polled. Used to add transfer progress
+/// to uploads without changing how their streams are consumed; downloads count their
+/// progress inline in [`download_response_to_file`].
+struct ProgressStream<S> {
+    inner: S,
+    callback: ProgressCallback,
+    bytes_so_far: u64,
+    total: Option<u64>
 }
 
+impl<S> ProgressStream<S> {
+    fn new(inner: S, total: Option<u64>, callback: ProgressCallback) -> Self {
+        ProgressStream { inner, callback, bytes_so_far: 0, total }
+    }
+}
+
+impl<S, T, E> Stream for ProgressStream<S>
+    where
+        S: Stream<Item=std::result::Result<T, E>> + Unpin,
+        T: AsRef<[u8]>
+{
+    type Item = std::result::Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        match Pin::new(&mut this.inner).poll_next(cx) {
+            Poll::Ready(Some(Ok(chunk))) => {
+                this.bytes_so_far += chunk.as_ref().len() as u64;
+                (this.callback)(this.bytes_so_far, this.total);
+                Poll::Ready(Some(Ok(chunk)))
+            }
+            other => other
+        }
+    }
+}
 
 pub(crate) async fn file_to_part(path: &PathBuf) -> io::Result<Part> {
-    let name = path.file_name()
-        .ok_or(Error::new(ErrorKind::InvalidInput,"filename is not full"))?
-        .to_str()
-        .ok_or(Error::new(ErrorKind::InvalidData,"non unicode filename"))?
-        .to_owned();
+    let name = file_name(path)?;
     let file = File::open(path).await?;
     let size = file.metadata().await?.len();
     let stream = FramedRead::new(file, BytesCodec::new());
     let body = Body::wrap_stream(stream);
     Ok(Part::stream_with_length(body,size).file_name(name))
+}
+
+/// Like [`file_to_part`], but reports upload progress through `progress` as the file
+/// is streamed, using its on-disk size (from `file.metadata().len()`) as the total.
+pub(crate) async fn file_to_part_with_progress(path: &PathBuf, progress: ProgressCallback) -> io::Result<Part> {
+    let name = file_name(path)?;
+    let file = File::open(path).await?;
+    let size = file.metadata().await?.len();
+    let stream = ProgressStream::new(FramedRead::new(file, BytesCodec::new()), Some(size), progress);
+    let body = Body::wrap_stream(stream);
+    Ok(Part::stream_with_length(body,size).file_name(name))
+}
+
+fn file_name(path: &PathBuf) -> io::Result<String> {
+    Ok(path.file_name()
+        .ok_or(io::Error::new(ErrorKind::InvalidInput,"filename is not full"))?
+        .to_str()
+        .ok_or(io::Error::new(ErrorKind::InvalidData,"non unicode filename"))?
+        .to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_sse_event_reads_a_complete_event() {
+        let mut buffer = BytesMut::from(&b"data: hello\n\n"[..]);
+        let event = next_sse_event(&mut buffer).expect("event should be present");
+        assert!(matches!(event, SseEvent::Data(data) if &data[..] == b"hello"));
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn next_sse_event_recognizes_done() {
+        let mut buffer = BytesMut::from(&b"data: [DONE]\n\n"[..]);
+        let event = next_sse_event(&mut buffer).expect("event should be present");
+        assert!(matches!(event, SseEvent::Done));
+    }
+
+    #[test]
+    fn next_sse_event_waits_on_a_partial_event() {
+        let mut buffer = BytesMut::from(&b"data: hel"[..]);
+        assert!(next_sse_event(&mut buffer).is_none());
+        assert_eq!(&buffer[..], b"data: hel");
+    }
+
+    #[test]
+    fn next_sse_event_skips_blocks_without_a_data_line() {
+        let mut buffer = BytesMut::from(&b": keep-alive\n\ndata: hello\n\n"[..]);
+        let event = next_sse_event(&mut buffer).expect("event should be present");
+        assert!(matches!(event, SseEvent::Data(data) if &data[..] == b"hello"));
+    }
+
+    #[test]
+    fn next_sse_event_reads_events_one_at_a_time() {
+        let mut buffer = BytesMut::from(&b"data: first\n\ndata: second\n\n"[..]);
+        let first = next_sse_event(&mut buffer).expect("first event should be present");
+        assert!(matches!(first, SseEvent::Data(data) if &data[..] == b"first"));
+        let second = next_sse_event(&mut buffer).expect("second event should be present");
+        assert!(matches!(second, SseEvent::Data(data) if &data[..] == b"second"));
+    }
+
+    #[test]
+    fn retry_delay_honors_retry_after_header() {
+        let policy = RetryPolicy::default();
+        let retry_after = HeaderValue::from_static("7");
+        let delay = retry_delay(&policy, 1, Some(&retry_after));
+        assert_eq!(delay, Duration::from_secs(7));
+    }
+
+    #[test]
+    fn retry_delay_backs_off_and_caps_at_max_backoff() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300)
+        };
+        for attempt in 1..=5 {
+            let delay = retry_delay(&policy, attempt, None);
+            // 100ms, 200ms, then capped at 300ms
+            let floor = (policy.initial_backoff * 2u32.pow(attempt - 1)).min(policy.max_backoff);
+            assert!(delay >= floor, "attempt {attempt} delay {delay:?} should be at least the backoff floor {floor:?}");
+            assert!(delay <= floor + Duration::from_millis(floor.as_millis() as u64 / 2),
+                "attempt {attempt} delay {delay:?} should not exceed the backoff floor plus jitter");
+        }
+    }
+
+    #[test]
+    fn cache_entry_round_trips_without_ttl() {
+        let encoded = encode_cache_entry(b"payload");
+        let decoded = decode_cache_entry(&encoded, None).expect("entry should decode");
+        assert_eq!(decoded, b"payload");
+    }
+
+    #[test]
+    fn cache_entry_expires_past_ttl() {
+        let mut encoded = encode_cache_entry(b"payload");
+        let ancient = 0u64.to_le_bytes();
+        encoded[..8].copy_from_slice(&ancient);
+        assert!(decode_cache_entry(&encoded, Some(Duration::from_secs(60))).is_none());
+    }
+
+    #[test]
+    fn decode_cache_entry_rejects_undersized_input() {
+        assert!(decode_cache_entry(&[0u8; 4], None).is_none());
+    }
+
+    #[test]
+    fn cache_key_is_deterministic_and_endpoint_sensitive() {
+        let request = serde_json::json!({"model": "text-embedding-3-small", "input": "hi"});
+        let key_a = cache_key("/embeddings", &request).expect("should hash");
+        let key_b = cache_key("/embeddings", &request).expect("should hash");
+        let key_c = cache_key("/moderations", &request).expect("should hash");
+        assert_eq!(key_a, key_b);
+        assert_ne!(key_a, key_c);
+    }
 }
\ No newline at end of file